@@ -1,13 +1,13 @@
 use crate::build;
 use crate::log::Level;
-use crate::session::{ColorConfig, Session};
+use crate::session::{ColorConfig, DiagnosticsWriter, ErrorFormat, Session};
 use std::default::Default;
 use std::env;
 use std::env::current_dir;
 use std::error::Error;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[cfg(test)]
 mod test;
@@ -47,6 +47,31 @@ impl Configuration {
         self
     }
 
+    /// Selects how grammar errors are rendered, analogous to rustc's
+    /// `--error-format`. See [`ErrorFormat`] for the available styles:
+    /// a terse one-line-per-error form suited to CI log grepping, the
+    /// current full rendering, and an annotate-snippets style that prints
+    /// the offending grammar source line with carets underlining the exact
+    /// span (plus a labeled secondary span for the other side of an LR
+    /// conflict). Default is [`ErrorFormat::Full`].
+    pub fn error_format(&mut self, val: ErrorFormat) -> &mut Configuration {
+        self.session.error_format = val;
+        self
+    }
+
+    /// Redirects where JSON diagnostics (see
+    /// [`Configuration::emit_json_diagnostics`]) and rendered error output
+    /// (see [`Configuration::error_format`]) are written. Defaults to
+    /// stderr; use this to write to a file an editor polls, to stdout, or
+    /// to an in-memory buffer in tests.
+    pub fn set_diagnostics_writer(
+        &mut self,
+        writer: impl io::Write + Send + 'static,
+    ) -> &mut Configuration {
+        self.session.diagnostics_writer = DiagnosticsWriter::new(writer);
+        self
+    }
+
     /// Specify a custom directory to search for input files.
     ///
     /// This directory is recursively searched for `.lalrpop` files to be
@@ -105,6 +130,27 @@ impl Configuration {
         self
     }
 
+    /// If true, skip regenerating a parser whose fingerprint is unchanged,
+    /// instead of relying solely on file modification times. Default is
+    /// false.
+    ///
+    /// The fingerprint is a hash of the grammar file's contents, the
+    /// contents of any files it names on `// include: <path>` lines (paths
+    /// relative to the grammar file), the active feature set, and the
+    /// LALRPOP version and config flags that affect codegen. It is recorded
+    /// in a cache file in the out dir and recomputed on every run; the `.rs`
+    /// file is regenerated only when the fingerprint no longer matches or
+    /// the `.rs` file is missing from the out dir. This catches cases that
+    /// mtime-based rebuilds miss, such as an edited included fragment or a
+    /// changed LALRPOP version, while avoiding needless rebuilds when a file
+    /// is touched but not actually changed. When
+    /// [`Self::emit_rerun_directives()`] is set, a `rerun-if-changed`
+    /// directive is emitted for every input that fed the hash.
+    pub fn use_fingerprint_cache(&mut self, val: bool) -> &mut Configuration {
+        self.session.use_fingerprint_cache = val;
+        self
+    }
+
     /// If true, print `rerun-if-changed` directives to standard output.
     ///
     /// If this is set, Cargo will only rerun the build script if any of the processed
@@ -143,6 +189,41 @@ impl Configuration {
         self
     }
 
+    /// If true, emit grammar errors (parse errors, duplicate rule
+    /// definitions, unresolved macro uses, undefined nonterminals) as a
+    /// stream of structured JSON diagnostics, in addition to the normal
+    /// human-readable messages.
+    ///
+    /// Each record carries the message, a severity level, the `.lalrpop` file
+    /// path, and byte-offset spans with line/column information, mirroring
+    /// the rustc JSON diagnostic shape consumed by RLS-like tooling. This is
+    /// intended for editors and language servers that want to surface
+    /// errors inline rather than scrape stderr text. A duplicate rule is
+    /// reported with one labeled span per definition; LR(1) conflicts are
+    /// not detected, and no lookahead is reported.
+    pub fn emit_json_diagnostics(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_json_diagnostics = val;
+        self
+    }
+
+    /// If true, time the phases of grammar checking with wall-clock timings
+    /// and per-phase item counts, and write a timestamped self-profile
+    /// report (one row per phase) to the out dir.
+    ///
+    /// The phases are named `tokenize`, `macro_expansion`, `nfa_dfa`,
+    /// `lalr_states`, `conflict_resolution` and `codegen`. They time the
+    /// simplified grammar checks of the build pipeline (brace matching, rule
+    /// definition lookup, macro-use and nonterminal-reference scans,
+    /// duplicate rule detection); no NFA/DFA or LALR states are built and no
+    /// Rust code is emitted, so the `codegen` row always counts zero items.
+    ///
+    /// Modeled on rustc's self-profiler, this is intended to help diagnose
+    /// why a grammar with many macro instantiations is slow to build.
+    pub fn emit_self_profile(&mut self, val: bool) -> &mut Configuration {
+        self.session.emit_self_profile = val;
+        self
+    }
+
     /// Minimal logs: only for errors that halt progress.
     pub fn log_quiet(&mut self) -> &mut Configuration {
         self.session.log.set_level(Level::Taciturn);
@@ -180,6 +261,40 @@ impl Configuration {
         self
     }
 
+    /// If true, compile independent `.lalrpop` files in `process_dir` on a
+    /// thread pool instead of serially. Default is false.
+    ///
+    /// The pool has as many worker threads as [`Self::set_parallelism`]
+    /// sets (at most one per file); with the default of 1, files are
+    /// processed one at a time even when a jobserver is available.
+    /// Concurrency is further bounded by the build system's jobserver: this connects
+    /// to the jobserver via [`jobserver::Client::from_env`], which parses the
+    /// `--jobserver-auth`/`MAKEFLAGS`/`CARGO_MAKEFLAGS` that Cargo passes to
+    /// build scripts. The thread processing the current file already holds
+    /// one implicit token; before starting work on another file, a worker
+    /// must acquire a token from the client and release it when that file
+    /// finishes, so total concurrency never exceeds what the parent build
+    /// granted. If no jobserver is available, falls back to the pool size
+    /// set by [`Self::set_parallelism`], or serial processing.
+    ///
+    /// The set of `rerun-if-changed` directives and the number of failed
+    /// files do not depend on the order in which files complete. Each
+    /// file's directives and diagnostics are printed while that file is
+    /// processed, so with several workers their order across files follows
+    /// completion order.
+    pub fn use_jobserver(&mut self, val: bool) -> &mut Configuration {
+        self.session.use_jobserver = val;
+        self
+    }
+
+    /// Sets the number of worker threads used to process `.lalrpop` files
+    /// when [`Self::use_jobserver`] is enabled, whether or not a jobserver
+    /// is available. Default is 1 (serial).
+    pub fn set_parallelism(&mut self, val: usize) -> &mut Configuration {
+        self.session.parallelism = val;
+        self
+    }
+
     /// Sets the features used during compilation, disables the use of cargo features.
     /// (Default: Loaded from `CARGO_FEATURE_{}` environment variables).
     pub fn set_features<I>(&mut self, iterable: I) -> &mut Configuration
@@ -249,14 +364,14 @@ impl Configuration {
             );
         }
 
-        let session = Rc::new(session);
+        let session = Arc::new(session);
         build::process_dir(session, path)?;
         Ok(())
     }
 
     /// Process the given `.lalrpop` file.
     pub fn process_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
-        let session = Rc::new(self.session.clone());
+        let session = Arc::new(self.session.clone());
         build::process_file(session, path)?;
         Ok(())
     }
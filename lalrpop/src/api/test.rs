@@ -0,0 +1,4 @@
+// Placeholder for `Configuration`-level integration tests. Unit coverage
+// for the individual pieces `Configuration` wires together (diagnostics,
+// emitters, the fingerprint cache, jobserver token accounting) lives next
+// to each of those modules instead.
@@ -0,0 +1,867 @@
+//! The build pipeline: discovers `.lalrpop` files, compiles each one, and
+//! reports any errors it finds through [`Session::report`].
+
+use crate::diagnostics::{line_col, Diagnostic, DiagnosticSpan, Severity};
+use crate::fingerprint;
+use crate::parallel::JobserverClient;
+use crate::profile::Profiler;
+use crate::session::Session;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// An error discovered while compiling a single `.lalrpop` file: a parse
+/// error, an LR(1) conflict, an unresolved macro use, or an undefined
+/// nonterminal.
+#[derive(Debug)]
+enum GrammarError {
+    Parse {
+        message: String,
+        span: (usize, usize),
+    },
+    /// Two rules share the same left-hand side. This is a proxy for the
+    /// shift/reduce and reduce/reduce conflicts LALRPOP's real LALR state
+    /// construction detects, not the conflict itself — we don't build LALR
+    /// states here, so we have no lookahead set to report and don't
+    /// pretend otherwise (see `find_duplicate_rules`).
+    DuplicateRule {
+        message: String,
+        span: (usize, usize),
+        other: (usize, usize),
+    },
+    UnresolvedMacroUse {
+        name: String,
+        span: (usize, usize),
+    },
+    UndefinedNonterminal {
+        name: String,
+        span: (usize, usize),
+    },
+}
+
+impl GrammarError {
+    fn to_diagnostic(&self, file: &Path, source: &str) -> Diagnostic {
+        let span = |start: usize, end: usize, label: Option<String>| {
+            let (line_start, column_start) = line_col(source, start);
+            let (line_end, column_end) = line_col(source, end);
+            let source_line = source.lines().nth(line_start.saturating_sub(1)).map(str::to_string);
+            DiagnosticSpan {
+                file_name: file.display().to_string(),
+                byte_start: start,
+                byte_end: end,
+                line_start,
+                column_start,
+                line_end,
+                column_end,
+                label,
+                source_line,
+            }
+        };
+
+        match self {
+            GrammarError::Parse { message, span: (s, e) } => {
+                Diagnostic::new(message.clone(), Severity::Error).with_span(span(*s, *e, None))
+            }
+            GrammarError::DuplicateRule {
+                message,
+                span: (s, e),
+                other: (os, oe),
+            } => Diagnostic::new(message.clone(), Severity::Error)
+                .with_span(span(*s, *e, Some("this rule".to_string())))
+                .with_span(span(*os, *oe, Some("conflicts with this one".to_string()))),
+            GrammarError::UnresolvedMacroUse { name, span: (s, e) } => Diagnostic::new(
+                format!("unresolved macro use `{name}`"),
+                Severity::Error,
+            )
+            .with_span(span(*s, *e, None)),
+            GrammarError::UndefinedNonterminal { name, span: (s, e) } => Diagnostic::new(
+                format!("undefined nonterminal `{name}`"),
+                Severity::Error,
+            )
+            .with_span(span(*s, *e, None)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BuildFailed {
+    file_count: usize,
+}
+
+impl fmt::Display for BuildFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lalrpop: errors in {} file(s)", self.file_count)
+    }
+}
+
+impl Error for BuildFailed {}
+
+/// Process all `.lalrpop` files found recursively under `path`.
+pub fn process_dir<P: AsRef<Path>>(session: Arc<Session>, path: P) -> Result<(), Box<dyn Error>> {
+    let mut files = Vec::new();
+    collect_lalrpop_files(path.as_ref(), &mut files)?;
+    files.sort();
+
+    let profiler = Profiler::default();
+    let client = JobserverClient::from_env(session.parallelism)?;
+    let results = if session.use_jobserver && files.len() > 1 && !client.is_starved() {
+        compile_parallel(&session, &files, &profiler, client)?
+    } else {
+        compile_serial(&session, &files, &profiler)?
+    };
+    write_self_profile(&session, &profiler);
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    if failed > 0 {
+        return Err(Box::new(BuildFailed { file_count: failed }));
+    }
+    Ok(())
+}
+
+/// Process a single `.lalrpop` file.
+pub fn process_file<P: AsRef<Path>>(session: Arc<Session>, path: P) -> Result<(), Box<dyn Error>> {
+    let profiler = Profiler::default();
+    let result = compile_one(&session, path.as_ref(), &profiler)?;
+    write_self_profile(&session, &profiler);
+    if result.is_err() {
+        return Err(Box::new(BuildFailed { file_count: 1 }));
+    }
+    Ok(())
+}
+
+fn compile_serial(
+    session: &Arc<Session>,
+    files: &[PathBuf],
+    profiler: &Profiler,
+) -> io::Result<Vec<Result<(), ()>>> {
+    let mut results = Vec::with_capacity(files.len());
+    for file in files {
+        results.push(compile_one(session, file, profiler)?);
+    }
+    Ok(results)
+}
+
+/// Compiles independent grammar files on a bounded pool of
+/// `min(session.parallelism, files.len())` worker threads, each pulling
+/// the next unclaimed file from a shared cursor until none remain.
+/// Workers additionally cooperate with the build system's jobserver:
+/// before starting work on any file beyond the very first one claimed
+/// pool-wide, a worker acquires a token from `JobserverClient` and
+/// releases it when that file is done, bounding concurrency a second time
+/// to the tokens the parent build granted (which may be fewer than
+/// `session.parallelism`). Results are recorded by each file's position in
+/// the sorted `files` list, so the final failure count and which files
+/// failed are deterministic regardless of the order in which threads
+/// complete.
+fn compile_parallel(
+    session: &Arc<Session>,
+    files: &[PathBuf],
+    profiler: &Profiler,
+    client: JobserverClient,
+) -> io::Result<Vec<Result<(), ()>>> {
+    let worker_count = session.parallelism.max(1).min(files.len());
+    let next_file = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<Result<(), ()>>>> = (0..files.len()).map(|_| Mutex::new(None)).collect();
+    let first_error: Mutex<Option<io::Error>> = Mutex::new(None);
+
+    std::thread::scope(|scope| {
+        let client = &client;
+        let next_file = &next_file;
+        let results = &results;
+        let first_error = &first_error;
+
+        for _ in 0..worker_count {
+            let session: &Session = session;
+            scope.spawn(move || loop {
+                let i = next_file.fetch_add(1, Ordering::SeqCst);
+                let Some(file) = files.get(i) else { break };
+
+                // The one implicit token the whole pool was given covers
+                // the very first file claimed; every other file must
+                // acquire its own before work starts on it.
+                let _token = if i == 0 {
+                    None
+                } else {
+                    match client.acquire() {
+                        Ok(token) => Some(token),
+                        Err(e) => {
+                            first_error.lock().unwrap().get_or_insert(e);
+                            break;
+                        }
+                    }
+                };
+
+                match compile_one(session, file, profiler) {
+                    Ok(result) => *results[i].lock().unwrap() = Some(result),
+                    Err(e) => {
+                        first_error.lock().unwrap().get_or_insert(e);
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(e) = first_error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    Ok(results
+        .into_iter()
+        .map(|r| r.into_inner().unwrap().expect("every file index is processed before the pool drains"))
+        .collect())
+}
+
+fn write_self_profile(session: &Session, profiler: &Profiler) {
+    if !session.emit_self_profile {
+        return;
+    }
+    if let Some(out_dir) = &session.out_dir {
+        if let Err(e) = profiler.write_report(out_dir) {
+            eprintln!("warning: failed to write self-profile report: {e}");
+        }
+    }
+}
+
+fn collect_lalrpop_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    if dir.is_file() {
+        out.push(dir.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            collect_lalrpop_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "lalrpop") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Compiles a single grammar file, reporting any diagnostics found. Returns
+/// `Ok(Err(()))` (rather than an `Err`) when the failure is a grammar error
+/// that was already reported, so the caller can keep aggregating across
+/// files instead of aborting on the first one.
+fn compile_one(
+    session: &Session,
+    file: &Path,
+    profiler: &Profiler,
+) -> io::Result<Result<(), ()>> {
+    let source = fs::read_to_string(file)?;
+    let includes = find_includes(&source, file);
+
+    if session.emit_rerun_directives {
+        println!("cargo:rerun-if-changed={}", file.display());
+        for include in &includes {
+            println!("cargo:rerun-if-changed={}", include.display());
+        }
+    }
+
+    let cached = if session.use_fingerprint_cache {
+        let fp = fingerprint::fingerprint(session, &source, &includes)?;
+        session
+            .out_dir
+            .as_deref()
+            .map(|out_dir| (fp, fingerprint::cache_path(out_dir, file), output_path(out_dir, file)))
+    } else {
+        None
+    };
+
+    if !session.force_build {
+        if let Some((fp, cache_path, output_path)) = &cached {
+            // Both the fingerprint and the artifact it describes must be
+            // present: a fingerprint match alone doesn't prove anything
+            // was actually emitted for this file (e.g. after `out_dir` was
+            // cleared but the cache file survived).
+            if fingerprint::read_cached(cache_path) == Some(*fp) && output_path.exists() {
+                // Nothing that feeds codegen for this file has changed.
+                return Ok(Ok(()));
+            }
+        }
+    }
+
+    let errors = check_grammar(&source, profiler);
+
+    if errors.is_empty() {
+        if let Some((fp, cache_path, output_path)) = &cached {
+            let _ = fingerprint::write_cached(cache_path, *fp);
+            let _ = fs::write(output_path, STUB_OUTPUT);
+        }
+        Ok(Ok(()))
+    } else {
+        for error in &errors {
+            session.report(&error.to_diagnostic(file, &source));
+        }
+        Ok(Err(()))
+    }
+}
+
+/// Placeholder written in place of a generated parser (see [`STUB_OUTPUT`]
+/// and [`output_path`]) — this pipeline has no codegen step, so the file
+/// exists only to give the fingerprint cache a real artifact to verify.
+const STUB_OUTPUT: &str = "// lalrpop: placeholder output.\n\
+// check_grammar only lints a grammar file; it never generates a parser\n\
+// from it (see that function's doc comment), so this file isn't one\n\
+// either. It exists so a cache-hit can confirm something was actually\n\
+// written for this grammar file, rather than trusting a fingerprint\n\
+// match alone after `out_dir` was cleared out from under it.\n";
+
+/// Path to the (stand-in) generated artifact for a grammar file. Checking
+/// for this file's existence, not just a fingerprint match, is what lets
+/// `compile_one` detect a cleared `out_dir` instead of silently skipping
+/// regeneration with nothing on disk.
+fn output_path(out_dir: &Path, file: &Path) -> PathBuf {
+    let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or("grammar");
+    out_dir.join(format!("{stem}.rs"))
+}
+
+/// Looks for `// include: <path>` directives used to pull in shared
+/// grammar fragments, the way `#[include]` does in LALRPOP grammars. Paths
+/// are resolved relative to the grammar file's own directory.
+fn find_includes(source: &str, file: &Path) -> Vec<PathBuf> {
+    let parent = file.parent().unwrap_or_else(|| Path::new("."));
+    source
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("// include:"))
+        .map(|rest| parent.join(rest.trim()))
+        .collect()
+}
+
+/// A deliberately simplified stand-in for LALRPOP's full lexer/parser/LALR
+/// pipeline: enough structural checks to exercise the diagnostic paths
+/// (parse errors, duplicate-rule conflicts, unresolved macro uses,
+/// undefined nonterminals) with real spans computed from the source text.
+/// It does **not** build an LALR automaton or emit a parser — there is no
+/// codegen here, simplified or otherwise, so the `emit_self_profile`,
+/// `emit_json_diagnostics`, jobserver-aware parallelism, and fingerprint
+/// cache this module wires up all instrument/gate *this lint pass*, not
+/// LALRPOP's real grammar-to-Rust compilation. Each major phase is timed
+/// through `profiler`.
+///
+/// Before scanning for identifiers, the source is masked twice: once to
+/// blank out string/char literals and comments (so a regex terminal like
+/// `r"a{2,3}"` can't be mistaken for an unbalanced brace or a macro use),
+/// and again to additionally blank the contents of `{ ... }` action-code
+/// blocks (so `Vec<T>` or `Box<dyn Error>` in a grammar action can't be
+/// mistaken for a macro invocation or an undefined nonterminal).
+fn check_grammar(source: &str, profiler: &Profiler) -> Vec<GrammarError> {
+    let mut errors = Vec::new();
+
+    let literal_masked = mask_literals_and_comments(source);
+
+    let braces = profiler.time("tokenize", source.split_whitespace().count(), || {
+        unbalanced_braces(&literal_masked)
+    });
+    if let Some(span) = braces {
+        errors.push(GrammarError::Parse {
+            message: "unbalanced braces in grammar".to_string(),
+            span,
+        });
+        // A grammar that doesn't even parse can't be checked further.
+        return errors;
+    }
+
+    let definitions = profiler.time_sized(
+        "macro_expansion",
+        || find_definitions(&literal_masked),
+        |defs| defs.len(),
+    );
+
+    let action_masked = mask_action_blocks(&literal_masked);
+
+    let macro_uses = profiler.time_sized(
+        "nfa_dfa",
+        || find_macro_uses(&action_masked),
+        |uses| uses.len(),
+    );
+    for (name, span) in &macro_uses {
+        if !definitions.contains(name) {
+            errors.push(GrammarError::UnresolvedMacroUse {
+                name: name.to_string(),
+                span: *span,
+            });
+        }
+    }
+
+    let nonterminal_refs = profiler.time_sized(
+        "lalr_states",
+        || find_nonterminal_refs(&action_masked),
+        |refs| refs.len(),
+    );
+    for (name, span) in &nonterminal_refs {
+        if !definitions.contains(name) {
+            errors.push(GrammarError::UndefinedNonterminal {
+                name: name.to_string(),
+                span: *span,
+            });
+        }
+    }
+
+    let conflicts = profiler.time_sized(
+        "conflict_resolution",
+        || find_duplicate_rules(&literal_masked),
+        |conflicts| conflicts.len(),
+    );
+    errors.extend(conflicts);
+
+    profiler.time("codegen", 0, || {
+        // No Rust code emission happens in this lint-only pipeline; this
+        // phase is kept (at zero cost) so a report diffed against a real
+        // codegen run makes the gap obvious rather than silently missing.
+    });
+
+    errors
+}
+
+/// Blanks the interior of string/char literals and `//`/`/* */` comments,
+/// replacing each byte with a space but leaving newlines untouched so
+/// line/column math over the result still matches the original source.
+/// Byte-for-byte masking keeps the result valid UTF-8 even though this
+/// scans one byte at a time, since every delimiter this function looks
+/// for (`"`, `'`, `/`, `*`, `#`) is ASCII and therefore can never be a
+/// continuation byte of a multi-byte UTF-8 sequence.
+fn mask_literals_and_comments(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let mut out = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    if bytes[i] != b'\n' {
+                        out[i] = b' ';
+                    }
+                    i += 1;
+                }
+                i = (i + 2).min(bytes.len());
+            }
+            b'r' if matches!(bytes.get(i + 1), Some(b'"') | Some(b'#')) => {
+                if let Some(end) = raw_string_end(bytes, i) {
+                    for k in i..end {
+                        if bytes[k] != b'\n' {
+                            out[k] = b' ';
+                        }
+                    }
+                    i = end;
+                } else {
+                    i += 1;
+                }
+            }
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    if i < bytes.len() {
+                        i += 1;
+                    }
+                }
+                i = (i + 1).min(bytes.len());
+                for k in start..i {
+                    if bytes[k] != b'\n' {
+                        out[k] = b' ';
+                    }
+                }
+            }
+            b'\'' => {
+                if let Some(end) = char_literal_end(bytes, i) {
+                    for k in i..=end {
+                        if bytes[k] != b'\n' {
+                            out[k] = b' ';
+                        }
+                    }
+                    i = end + 1;
+                } else {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    String::from_utf8(out).expect("only ASCII bytes were replaced with ASCII spaces")
+}
+
+/// Finds the end (exclusive) of a raw string literal starting at `start`
+/// (pointing at the `r`), or `None` if `start` isn't actually one (e.g. an
+/// identifier that merely begins with `r`).
+fn raw_string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    let mut hashes = 0;
+    while bytes.get(i) == Some(&b'#') {
+        hashes += 1;
+        i += 1;
+    }
+    if bytes.get(i) != Some(&b'"') {
+        return None;
+    }
+    i += 1;
+    loop {
+        if i >= bytes.len() {
+            return Some(bytes.len());
+        }
+        if bytes[i] == b'"' && bytes[i + 1..].iter().take(hashes).all(|&b| b == b'#') {
+            return Some(i + 1 + hashes);
+        }
+        i += 1;
+    }
+}
+
+/// Finds the closing `'` of a char literal starting at `start`, or `None`
+/// if this is actually a lifetime (`'a`) rather than a literal.
+fn char_literal_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if i >= bytes.len() {
+        return None;
+    }
+    if bytes[i] == b'\\' {
+        i += 1;
+        while i < bytes.len() && bytes[i] != b'\'' && i < start + 10 {
+            i += 1;
+        }
+    } else {
+        i += 1;
+        while i < bytes.len() && (bytes[i] & 0xC0) == 0x80 {
+            i += 1;
+        }
+    }
+    if bytes.get(i) == Some(&b'\'') {
+        Some(i)
+    } else {
+        None
+    }
+}
+
+/// Given source already passed through [`mask_literals_and_comments`],
+/// additionally blanks the contents of every `{ ... }` action-code block
+/// (tracking nesting depth), since LALRPOP grammars only use braces to
+/// delimit Rust action code — any identifiers inside them belong to the
+/// target language, not the grammar.
+fn mask_action_blocks(literal_masked: &str) -> String {
+    let bytes = literal_masked.as_bytes();
+    let mut out = bytes.to_vec();
+    let mut depth: i32 = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'{' => depth += 1,
+            b'}' => depth = (depth - 1).max(0),
+            b'\n' => {}
+            _ if depth > 0 => out[i] = b' ',
+            _ => {}
+        }
+    }
+    String::from_utf8(out).expect("only ASCII bytes were replaced with ASCII spaces")
+}
+
+fn unbalanced_braces(source: &str) -> Option<(usize, usize)> {
+    let mut depth: i32 = 0;
+    let mut open_at = 0;
+    for (i, c) in source.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    open_at = i;
+                }
+                depth += 1;
+            }
+            '}' => {
+                depth -= 1;
+                if depth < 0 {
+                    return Some((i, i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        Some((open_at, open_at + 1))
+    } else {
+        None
+    }
+}
+
+/// `Name: Type = { ... };` style rule definitions.
+fn find_definitions(source: &str) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if let Some(colon) = trimmed.find(':') {
+            let candidate = trimmed[..colon].trim();
+            if is_identifier(candidate) {
+                names.insert(candidate);
+            }
+        }
+    }
+    names
+}
+
+/// `Name<...>` macro invocations.
+fn find_macro_uses(source: &str) -> Vec<(&str, (usize, usize))> {
+    let mut uses = Vec::new();
+    let bytes = source.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii_alphabetic() || bytes[i] == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            if i < bytes.len() && bytes[i] == b'<' {
+                uses.push((&source[start..i], (start, i)));
+            }
+        } else {
+            i += 1;
+        }
+    }
+    uses
+}
+
+/// Bare identifier references that look like nonterminal uses (a
+/// capitalized word not followed by `<` or `:`), used only to seed the
+/// undefined-nonterminal diagnostic path.
+fn find_nonterminal_refs(source: &str) -> Vec<(&str, (usize, usize))> {
+    let mut refs = Vec::new();
+    let bytes = source.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii_uppercase() {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            let next = source[i..].trim_start();
+            if !next.starts_with(':') && !next.starts_with('<') {
+                refs.push((&source[start..i], (start, i)));
+            }
+        } else {
+            i += 1;
+        }
+    }
+    refs
+}
+
+/// Two rules sharing the same left-hand side are treated as a duplicate
+/// rule error: a simplified proxy for the shift/reduce and reduce/reduce
+/// conflicts that LALRPOP's real LALR state construction would detect. We
+/// don't build LALR states here, so unlike a real conflict diagnostic we
+/// have no lookahead set to report — the message says "duplicate rule",
+/// not "conflict", and carries no lookahead field, rather than fabricate
+/// one.
+fn find_duplicate_rules(source: &str) -> Vec<GrammarError> {
+    let mut seen: Vec<(&str, usize)> = Vec::new();
+    let mut errors = Vec::new();
+    let mut offset = 0;
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if let Some(colon) = trimmed.find(':') {
+            let candidate = trimmed[..colon].trim();
+            if is_identifier(candidate) {
+                let name_start = offset + line.len() - trimmed.len();
+                if let Some(&(_, other_start)) = seen.iter().find(|(n, _)| *n == candidate) {
+                    errors.push(GrammarError::DuplicateRule {
+                        message: format!("rule `{candidate}` is defined more than once"),
+                        span: (name_start, name_start + candidate.len()),
+                        other: (other_start, other_start + candidate.len()),
+                    });
+                } else {
+                    seen.push((candidate, name_start));
+                }
+            }
+        }
+        offset += line.len() + 1;
+    }
+    errors
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brace_quantifier_in_regex_literal_is_not_unbalanced() {
+        let source = r#"Num: () = r"a{2,3}" => ();"#;
+        assert_eq!(unbalanced_braces(&mask_literals_and_comments(source)), None);
+    }
+
+    #[test]
+    fn brace_quantifier_in_raw_string_with_hashes_is_not_unbalanced() {
+        let source = r##"Num: () = r#"a{2,3}"# => ();"##;
+        assert_eq!(unbalanced_braces(&mask_literals_and_comments(source)), None);
+    }
+
+    #[test]
+    fn brace_in_line_comment_is_not_unbalanced() {
+        let source = "Num: () = { () }; // unmatched } here\n";
+        assert_eq!(unbalanced_braces(&mask_literals_and_comments(source)), None);
+    }
+
+    #[test]
+    fn brace_in_block_comment_is_not_unbalanced() {
+        let source = "Num: () = { () }; /* unmatched { here */";
+        assert_eq!(unbalanced_braces(&mask_literals_and_comments(source)), None);
+    }
+
+    #[test]
+    fn a_real_unbalanced_brace_is_still_caught() {
+        let source = "Num: () = { () ;";
+        assert!(unbalanced_braces(&mask_literals_and_comments(source)).is_some());
+    }
+
+    #[test]
+    fn generic_type_in_action_block_is_not_a_macro_use_or_nonterminal_ref() {
+        let source = "Num: u32 = { Box::<dyn Error>::new(Vec::new()) };";
+        let masked = mask_action_blocks(&mask_literals_and_comments(source));
+        assert!(find_macro_uses(&masked).is_empty());
+        assert!(find_nonterminal_refs(&masked).is_empty());
+    }
+
+    #[test]
+    fn macro_use_outside_action_block_is_still_found() {
+        let source = "List: () = Comma<Num> => ();";
+        let masked = mask_action_blocks(&mask_literals_and_comments(source));
+        let uses = find_macro_uses(&masked);
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].0, "Comma");
+    }
+
+    #[test]
+    fn duplicate_rule_reports_no_fabricated_lookahead() {
+        let source = "Num: () = ();\nNum: () = ();\n";
+        let errors = find_duplicate_rules(&mask_literals_and_comments(source));
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            GrammarError::DuplicateRule { message, .. } => {
+                assert!(message.contains("defined more than once"));
+            }
+            other => panic!("expected DuplicateRule, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn lifetime_is_not_mistaken_for_a_char_literal() {
+        let source = "Num: &'a str = { x };";
+        // If the `'a` lifetime were treated as an unterminated char literal,
+        // everything after it (including the closing `;`) would be masked
+        // away and the brace count would go wrong.
+        let masked = mask_literals_and_comments(source);
+        assert_eq!(unbalanced_braces(&masked), None);
+    }
+
+    /// A scratch directory under the system temp dir, unique to this test
+    /// process and removed on drop, so fingerprint-cache tests can exercise
+    /// real file I/O without depending on a shared fixture path.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!(
+                "lalrpop-build-test-{name}-{}-{:?}",
+                std::process::id(),
+                std::thread::current().id()
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn session_with_fingerprint_cache(out_dir: &Path) -> Session {
+        Session {
+            use_fingerprint_cache: true,
+            out_dir: Some(out_dir.to_path_buf()),
+            ..Session::default()
+        }
+    }
+
+    #[test]
+    fn cache_hit_is_trusted_when_the_artifact_still_exists() {
+        let dir = TempDir::new("cache-hit");
+        let grammar = dir.path().join("g.lalrpop");
+        fs::write(&grammar, "Num: () = ();").unwrap();
+        let session = session_with_fingerprint_cache(dir.path());
+        let profiler = Profiler::default();
+
+        assert_eq!(compile_one(&session, &grammar, &profiler).unwrap(), Ok(()));
+        assert!(output_path(dir.path(), &grammar).exists());
+
+        // A second run should hit the cache rather than re-running
+        // `check_grammar`; it still reports success either way, but we
+        // confirm the artifact this cache hit relies on is genuinely there.
+        assert_eq!(compile_one(&session, &grammar, &profiler).unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn cache_hit_is_not_trusted_once_the_artifact_is_gone() {
+        let dir = TempDir::new("cache-miss-on-missing-artifact");
+        let grammar = dir.path().join("g.lalrpop");
+        fs::write(&grammar, "Num: () = ();").unwrap();
+        let session = session_with_fingerprint_cache(dir.path());
+        let profiler = Profiler::default();
+
+        assert_eq!(compile_one(&session, &grammar, &profiler).unwrap(), Ok(()));
+
+        // Simulate a cleared `out_dir` that left the `.fingerprint` file
+        // behind (e.g. restored from a stale incremental-build cache)
+        // without the generated artifact it describes.
+        fs::remove_file(output_path(dir.path(), &grammar)).unwrap();
+
+        // compile_one must re-run and re-emit the artifact rather than
+        // silently trusting the surviving fingerprint.
+        assert_eq!(compile_one(&session, &grammar, &profiler).unwrap(), Ok(()));
+        assert!(output_path(dir.path(), &grammar).exists());
+    }
+
+    #[test]
+    fn compile_parallel_processes_more_files_than_worker_threads() {
+        let dir = TempDir::new("bounded-pool");
+        let mut files = Vec::new();
+        for i in 0..5 {
+            let path = dir.path().join(format!("g{i}.lalrpop"));
+            fs::write(&path, format!("Num{i}: () = ();")).unwrap();
+            files.push(path);
+        }
+        // Fewer workers than files: this only exercises a real pool (each
+        // thread pulling more than one file from the shared cursor) if
+        // `compile_parallel` is actually bounded rather than spawning one
+        // thread per file.
+        let session = Arc::new(Session {
+            parallelism: 2,
+            ..Session::default()
+        });
+        let profiler = Profiler::default();
+        let client = JobserverClient::from_env(session.parallelism).unwrap();
+
+        let results = compile_parallel(&session, &files, &profiler, client).unwrap();
+        assert_eq!(results, vec![Ok(()); 5]);
+    }
+}
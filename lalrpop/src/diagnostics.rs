@@ -0,0 +1,194 @@
+//! Structured diagnostic records for grammar errors.
+//!
+//! This mirrors the shape of rustc's `--error-format=json` diagnostics
+//! (a message plus an array of spans, each carrying `file_name`,
+//! `byte_start`/`byte_end`, and `line_start`/`column_start`) so that
+//! editors and language servers that already consume rustc's JSON
+//! diagnostics can reuse the same plumbing for LALRPOP.
+
+use std::io::{self, Write};
+
+/// Severity of a [`Diagnostic`]. `Warning` and `Note` exist for parity with
+/// rustc's diagnostic levels; the simplified checks in `build.rs` only ever
+/// emit `Error` today.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+/// A span of grammar source referenced by a diagnostic, with both byte
+/// offsets and line/column information.
+#[derive(Clone, Debug)]
+pub struct DiagnosticSpan {
+    pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line_start: usize,
+    pub column_start: usize,
+    pub line_end: usize,
+    pub column_end: usize,
+    /// A label for this span, e.g. naming which side of an LR conflict it is.
+    pub label: Option<String>,
+    /// The full source line this span starts on, used by the
+    /// annotate-snippets style rendering to draw a caret underline.
+    pub source_line: Option<String>,
+}
+
+/// A single structured diagnostic: a grammar parse error, a duplicate rule
+/// definition, an unresolved macro use, or an undefined nonterminal.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    pub severity: Severity,
+    pub spans: Vec<DiagnosticSpan>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, severity: Severity) -> Self {
+        Diagnostic {
+            message: message.into(),
+            severity,
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn with_span(mut self, span: DiagnosticSpan) -> Self {
+        self.spans.push(span);
+        self
+    }
+
+    /// Writes this diagnostic as a single line of JSON, matching the
+    /// one-record-per-line stream rustc produces with `--error-format=json`.
+    pub fn write_json(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(
+            out,
+            "{{\"message\":{},\"level\":{},\"spans\":[",
+            json_string(&self.message),
+            json_string(self.severity.as_str())
+        )?;
+        for (i, span) in self.spans.iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(
+                out,
+                "{{\"file_name\":{},\"byte_start\":{},\"byte_end\":{},\
+                 \"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{},\
+                 \"label\":{}}}",
+                json_string(&span.file_name),
+                span.byte_start,
+                span.byte_end,
+                span.line_start,
+                span.column_start,
+                span.line_end,
+                span.column_end,
+                span.label
+                    .as_deref()
+                    .map(json_string)
+                    .unwrap_or_else(|| "null".to_string()),
+            )?;
+        }
+        writeln!(out, "]}}")
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Converts a byte offset into 1-based line/column numbers, the way
+/// rustc's JSON spans do.
+pub fn line_col(source: &str, byte_offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut col = 1;
+    for c in source[..byte_offset.min(source.len())].chars() {
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (line, col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_string_escapes_quotes_and_backslashes() {
+        assert_eq!(json_string(r#"say "hi"\now"#), r#""say \"hi\"\\now""#);
+    }
+
+    #[test]
+    fn json_string_escapes_control_characters() {
+        assert_eq!(json_string("a\nb\tc"), r#""a\nb\tc""#);
+        assert_eq!(json_string("\x01"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn line_col_at_start_of_file() {
+        assert_eq!(line_col("abc\ndef", 0), (1, 1));
+    }
+
+    #[test]
+    fn line_col_after_newlines() {
+        assert_eq!(line_col("abc\ndef\nghi", 4), (2, 1));
+        assert_eq!(line_col("abc\ndef\nghi", 9), (3, 2));
+    }
+
+    #[test]
+    fn line_col_clamps_past_end_of_source() {
+        assert_eq!(line_col("abc", 100), (1, 4));
+    }
+
+    #[test]
+    fn write_json_round_trips_message_and_spans() {
+        let diag = Diagnostic::new("oops", Severity::Error).with_span(DiagnosticSpan {
+            file_name: "g.lalrpop".to_string(),
+            byte_start: 0,
+            byte_end: 3,
+            line_start: 1,
+            column_start: 1,
+            line_end: 1,
+            column_end: 4,
+            label: Some("here".to_string()),
+            source_line: None,
+        });
+        let mut out = Vec::new();
+        diag.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains(r#""message":"oops""#));
+        assert!(json.contains(r#""level":"error""#));
+        assert!(json.contains(r#""file_name":"g.lalrpop""#));
+        assert!(json.contains(r#""label":"here""#));
+        assert!(json.ends_with("]}\n"));
+    }
+}
@@ -0,0 +1,115 @@
+//! Content-hash fingerprinting, so a parser whose inputs are unchanged can
+//! be skipped even if its `.rs` file's mtime looks stale, and so an edited
+//! dependency invalidates the cache even if the grammar file's own mtime
+//! didn't change.
+
+use crate::session::Session;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// LALRPOP's own version, mixed into every fingerprint so that upgrading
+/// LALRPOP invalidates the cache even when the grammar itself is
+/// unchanged.
+const LALRPOP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Computes a fingerprint over the grammar file's contents, the contents
+/// of every file it includes, the active feature set, and the LALRPOP
+/// version and config flags that affect codegen.
+pub(crate) fn fingerprint(
+    session: &Session,
+    source: &str,
+    includes: &[PathBuf],
+) -> io::Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    LALRPOP_VERSION.hash(&mut hasher);
+    session.emit_comments.hash(&mut hasher);
+    session.emit_whitespace.hash(&mut hasher);
+    session.macro_recursion_limit.hash(&mut hasher);
+    if let Some(features) = &session.features {
+        let mut sorted: Vec<&String> = features.iter().collect();
+        sorted.sort();
+        sorted.hash(&mut hasher);
+    }
+    source.hash(&mut hasher);
+
+    for include in includes {
+        fs::read_to_string(include)?.hash(&mut hasher);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Path to the cached fingerprint for a given grammar file.
+pub(crate) fn cache_path(out_dir: &Path, file: &Path) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("grammar");
+    out_dir.join(format!("{stem}.fingerprint"))
+}
+
+/// Reads the cached fingerprint, if any.
+pub(crate) fn read_cached(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Writes the fingerprint to the cache.
+pub(crate) fn write_cached(path: &Path, fingerprint: u64) -> io::Result<()> {
+    fs::write(path, fingerprint.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_source_and_config_fingerprint_identically() {
+        let session = Session::default();
+        let a = fingerprint(&session, "Num: () = ();", &[]).unwrap();
+        let b = fingerprint(&session, "Num: () = ();", &[]).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn different_source_fingerprints_differently() {
+        let session = Session::default();
+        let a = fingerprint(&session, "Num: () = ();", &[]).unwrap();
+        let b = fingerprint(&session, "Other: () = ();", &[]).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn different_config_fingerprints_differently_for_identical_source() {
+        let a_session = Session {
+            emit_comments: false,
+            ..Session::default()
+        };
+        let b_session = Session {
+            emit_comments: true,
+            ..Session::default()
+        };
+        let a = fingerprint(&a_session, "Num: () = ();", &[]).unwrap();
+        let b = fingerprint(&b_session, "Num: () = ();", &[]).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn read_cached_round_trips_through_write_cached() {
+        let dir = std::env::temp_dir().join(format!(
+            "lalrpop-fingerprint-test-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("g.fingerprint");
+
+        assert_eq!(read_cached(&path), None);
+        write_cached(&path, 42).unwrap();
+        assert_eq!(read_cached(&path), Some(42));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,16 @@
+//! LALRPOP: a parser generator for Rust.
+//!
+//! See [`Configuration`] for the `build.rs` entry point.
+
+pub mod api;
+mod build;
+mod diagnostics;
+mod fingerprint;
+mod log;
+mod parallel;
+mod profile;
+mod session;
+
+#[allow(deprecated)]
+pub use api::process_root_unconditionally;
+pub use api::{process_root, process_src, Configuration};
@@ -0,0 +1,26 @@
+//! Logging verbosity levels for the build pipeline.
+
+/// How chatty LALRPOP should be while processing grammar files.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Level {
+    /// Only errors that halt progress.
+    Taciturn,
+    /// High-level indications of progress (default).
+    #[default]
+    Informative,
+    /// More than info, but still not overwhelming.
+    Verbose,
+    /// Intended for debugging LALRPOP itself.
+    Debug,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Log {
+    level: Level,
+}
+
+impl Log {
+    pub fn set_level(&mut self, level: Level) {
+        self.level = level;
+    }
+}
@@ -0,0 +1,94 @@
+//! A thin wrapper around the [`jobserver`] crate, used to bound the
+//! concurrency of parallel grammar compilation to the token budget the
+//! parent build granted via `--jobserver-auth`/`MAKEFLAGS`/`CARGO_MAKEFLAGS`.
+//!
+//! Falls back to a local, fixed-size pool (also backed by `jobserver`,
+//! via [`jobserver::Client::new`]) when no jobserver is reachable (not
+//! running under `cargo`/`make`, or on a platform without the pipe-based
+//! protocol).
+
+use jobserver::Client;
+use std::io;
+
+/// A jobserver client, either inherited from the parent build or a local
+/// fallback pool.
+pub(crate) struct JobserverClient {
+    client: Client,
+    /// The capacity of the local fallback pool, or `None` when `client`
+    /// was inherited from a real `make`/`cargo` jobserver (whose capacity
+    /// we don't know and shouldn't guess at).
+    local_capacity: Option<usize>,
+}
+
+/// A token acquired from the jobserver. The thread that started work on
+/// the current file already holds the one implicit token it was given;
+/// this guard is only used for *additional* concurrent work, and returns
+/// its token to the jobserver when dropped.
+pub(crate) struct Acquired(#[allow(dead_code)] jobserver::Acquired);
+
+impl JobserverClient {
+    /// Connects to the jobserver Cargo/make passed down via
+    /// `--jobserver-auth`/`MAKEFLAGS`/`CARGO_MAKEFLAGS`, if any; otherwise
+    /// creates a local pool bounded by `parallelism`.
+    pub(crate) fn from_env(parallelism: usize) -> io::Result<JobserverClient> {
+        // Safety: called once, before any other file descriptors that
+        // could collide with an inherited jobserver pipe are opened.
+        match unsafe { Client::from_env() } {
+            Some(client) => Ok(JobserverClient {
+                client,
+                local_capacity: None,
+            }),
+            None => {
+                let capacity = parallelism.saturating_sub(1);
+                Ok(JobserverClient {
+                    client: Client::new(capacity)?,
+                    local_capacity: Some(capacity),
+                })
+            }
+        }
+    }
+
+    /// Returns `true` if this client can never grant an additional token:
+    /// no jobserver pipe was found, and the local fallback pool was
+    /// created with no spare capacity. Callers should fall back to serial
+    /// processing rather than spawn workers that would block on
+    /// `acquire()` forever.
+    pub(crate) fn is_starved(&self) -> bool {
+        self.local_capacity == Some(0)
+    }
+
+    /// Blocks until a token is available, then returns a guard that
+    /// releases it on drop.
+    pub(crate) fn acquire(&self) -> io::Result<Acquired> {
+        self.client.acquire().map(Acquired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_pool_with_parallelism_one_is_starved() {
+        let client = JobserverClient::from_env(1).unwrap();
+        assert!(client.is_starved());
+    }
+
+    #[test]
+    fn local_pool_with_spare_parallelism_is_not_starved() {
+        let client = JobserverClient::from_env(4).unwrap();
+        assert!(!client.is_starved());
+    }
+
+    #[test]
+    fn local_pool_grants_and_reclaims_tokens() {
+        let client = JobserverClient::from_env(2).unwrap();
+        // Capacity is 1 (parallelism - 1 for the implicit token already
+        // held by the calling thread): one token can be acquired...
+        let token = client.acquire().unwrap();
+        // ...and once it's released, another acquire succeeds again
+        // rather than hanging, proving the drop impl returns it.
+        drop(token);
+        client.acquire().unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! A lightweight self-profiler for the build pipeline, modeled on rustc's
+//! `SelfProfiler`: a scoped timer that records start/stop events per named
+//! phase and aggregates wall-clock duration and item counts (tokens,
+//! states, productions) so a timestamped report can be written to the out
+//! dir.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+struct PhaseRecord {
+    name: &'static str,
+    duration: Duration,
+    items: usize,
+}
+
+/// A phase's timing and item count, aggregated across every file compiled
+/// in this run.
+struct PhaseTotal {
+    calls: usize,
+    duration: Duration,
+    items: usize,
+}
+
+/// Accumulates timing for the named phases of compiling one or more
+/// grammar files. Uses a `Mutex` rather than a `RefCell` so it can be
+/// shared across the worker threads `build::process_dir` spawns when
+/// parallel processing is enabled.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    phases: Mutex<Vec<PhaseRecord>>,
+}
+
+impl Profiler {
+    /// Times `f`, recording its wall-clock duration under `name` along with
+    /// `items` processed in this phase (e.g. tokens, states, productions).
+    /// Use this when the item count is known before `f` runs; when it's a
+    /// property of `f`'s own result (e.g. how many definitions it found),
+    /// use [`Profiler::time_sized`] instead so the count reflects real
+    /// work rather than a guess made ahead of time.
+    pub(crate) fn time<T>(&self, name: &'static str, items: usize, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.phases.lock().unwrap().push(PhaseRecord {
+            name,
+            duration: start.elapsed(),
+            items,
+        });
+        result
+    }
+
+    /// Like [`Profiler::time`], but derives the item count from `f`'s own
+    /// result via `items`, e.g. `|definitions| definitions.len()`.
+    pub(crate) fn time_sized<T>(
+        &self,
+        name: &'static str,
+        f: impl FnOnce() -> T,
+        items: impl FnOnce(&T) -> usize,
+    ) -> T {
+        let start = Instant::now();
+        let result = f();
+        let count = items(&result);
+        self.phases.lock().unwrap().push(PhaseRecord {
+            name,
+            duration: start.elapsed(),
+            items: count,
+        });
+        result
+    }
+
+    /// Aggregates every recorded event by phase name (summing duration and
+    /// item counts, counting how many files passed through that phase),
+    /// and writes one row per phase to a timestamped file under `out_dir`.
+    /// Phases appear in the order they were first recorded.
+    pub(crate) fn write_report(&self, out_dir: &Path) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = out_dir.join(format!("lalrpop-self-profile-{timestamp}.tsv"));
+        let mut file = File::create(path)?;
+        writeln!(file, "phase\tcalls\tduration_us\titems")?;
+        for (name, total) in self.aggregate() {
+            writeln!(file, "{}\t{}\t{}\t{}", name, total.calls, total.duration.as_micros(), total.items)?;
+        }
+        Ok(())
+    }
+
+    /// Sums every recorded event into one [`PhaseTotal`] per phase name,
+    /// preserving first-seen order.
+    fn aggregate(&self) -> Vec<(&'static str, PhaseTotal)> {
+        let mut order = Vec::new();
+        let mut totals: HashMap<&'static str, PhaseTotal> = HashMap::new();
+        for phase in self.phases.lock().unwrap().iter() {
+            totals
+                .entry(phase.name)
+                .and_modify(|t| {
+                    t.calls += 1;
+                    t.duration += phase.duration;
+                    t.items += phase.items;
+                })
+                .or_insert_with(|| {
+                    order.push(phase.name);
+                    PhaseTotal {
+                        calls: 1,
+                        duration: phase.duration,
+                        items: phase.items,
+                    }
+                });
+        }
+        order
+            .into_iter()
+            .map(|name| (name, totals.remove(name).expect("just inserted")))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates_multiple_calls_to_the_same_phase_into_one_row() {
+        let profiler = Profiler::default();
+        profiler.time("tokenize", 10, || ());
+        profiler.time("tokenize", 20, || ());
+        profiler.time("macro_expansion", 3, || ());
+
+        let totals = profiler.aggregate();
+        assert_eq!(totals.len(), 2);
+
+        let (name, tokenize) = &totals[0];
+        assert_eq!(*name, "tokenize");
+        assert_eq!(tokenize.calls, 2);
+        assert_eq!(tokenize.items, 30);
+
+        let (name, macro_expansion) = &totals[1];
+        assert_eq!(*name, "macro_expansion");
+        assert_eq!(macro_expansion.calls, 1);
+        assert_eq!(macro_expansion.items, 3);
+    }
+
+    #[test]
+    fn time_sized_derives_items_from_the_result() {
+        let profiler = Profiler::default();
+        let result = profiler.time_sized("macro_expansion", || vec![1, 2, 3], |v| v.len());
+        assert_eq!(result, vec![1, 2, 3]);
+        let totals = profiler.aggregate();
+        assert_eq!(totals[0].1.items, 3);
+    }
+
+    #[test]
+    fn first_seen_order_is_preserved_across_aggregation() {
+        let profiler = Profiler::default();
+        profiler.time("lalr_states", 1, || ());
+        profiler.time("tokenize", 1, || ());
+        profiler.time("lalr_states", 1, || ());
+
+        let names: Vec<&str> = profiler.aggregate().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["lalr_states", "tokenize"]);
+    }
+}
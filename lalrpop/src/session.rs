@@ -0,0 +1,284 @@
+use crate::diagnostics::Diagnostic;
+use crate::log::Log;
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Where [`Session::report`] writes JSON diagnostics and rendered error
+/// output. Defaults to stderr; override with
+/// [`crate::api::Configuration::set_diagnostics_writer`] to redirect
+/// output to a file an editor polls, to stdout, or anywhere else
+/// implementing `Write`. Wrapped in a mutex so it can be shared across the
+/// worker threads `compile_parallel` spawns.
+#[derive(Clone)]
+pub(crate) struct DiagnosticsWriter(Arc<Mutex<dyn io::Write + Send>>);
+
+impl DiagnosticsWriter {
+    pub(crate) fn new(writer: impl io::Write + Send + 'static) -> Self {
+        DiagnosticsWriter(Arc::new(Mutex::new(writer)))
+    }
+
+    fn with_lock<R>(&self, f: impl FnOnce(&mut dyn io::Write) -> R) -> R {
+        let mut guard = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut *guard)
+    }
+}
+
+impl Default for DiagnosticsWriter {
+    fn default() -> Self {
+        DiagnosticsWriter::new(io::stderr())
+    }
+}
+
+/// Controls whether LALRPOP emits ANSI color codes in its output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ColorConfig {
+    Yes,
+    No,
+    #[default]
+    IfTty,
+}
+
+/// Selects how grammar diagnostics are rendered, analogous to rustc's
+/// `--error-format`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// One line per error: `file:line:col: message`. Easy to grep in CI logs.
+    Short,
+    /// The full, multi-line rendering LALRPOP has always used.
+    #[default]
+    Full,
+    /// Prints the offending grammar source line with carets underlining the
+    /// exact span, plus a labeled secondary span for the other side of an
+    /// LR conflict, in the style of the `annotate-snippets` crate.
+    Annotated,
+}
+
+/// Renders a [`Diagnostic`] in a particular style. Each [`ErrorFormat`]
+/// maps onto one implementation; [`Session::report`] dispatches to the
+/// right one instead of formatting diagnostics ad hoc at each call site.
+trait Emitter {
+    fn emit(&self, diag: &Diagnostic, out: &mut dyn io::Write) -> io::Result<()>;
+}
+
+struct ShortEmitter;
+
+impl Emitter for ShortEmitter {
+    fn emit(&self, diag: &Diagnostic, out: &mut dyn io::Write) -> io::Result<()> {
+        match diag.spans.first() {
+            Some(span) => writeln!(
+                out,
+                "{}:{}:{}: {}",
+                span.file_name, span.line_start, span.column_start, diag.message
+            ),
+            None => writeln!(out, "{}", diag.message),
+        }
+    }
+}
+
+struct FullEmitter;
+
+impl Emitter for FullEmitter {
+    fn emit(&self, diag: &Diagnostic, out: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(out, "{}: {}", diag.severity.as_str(), diag.message)?;
+        for span in &diag.spans {
+            writeln!(out, "  --> {}:{}:{}", span.file_name, span.line_start, span.column_start)?;
+            if let Some(label) = &span.label {
+                writeln!(out, "      {label}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct AnnotatedEmitter;
+
+impl Emitter for AnnotatedEmitter {
+    fn emit(&self, diag: &Diagnostic, out: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(out, "{}: {}", diag.severity.as_str(), diag.message)?;
+        for span in &diag.spans {
+            writeln!(out, "  --> {}:{}:{}", span.file_name, span.line_start, span.column_start)?;
+            if let Some(source_line) = &span.source_line {
+                writeln!(out, "   | {source_line}")?;
+                let underline_len = span.column_end.saturating_sub(span.column_start).max(1);
+                writeln!(
+                    out,
+                    "   | {}{}",
+                    " ".repeat(span.column_start.saturating_sub(1)),
+                    "^".repeat(underline_len)
+                )?;
+            }
+            if let Some(label) = &span.label {
+                writeln!(out, "   = note: {label}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn emitter_for(format: ErrorFormat) -> Box<dyn Emitter> {
+    match format {
+        ErrorFormat::Short => Box::new(ShortEmitter),
+        ErrorFormat::Full => Box::new(FullEmitter),
+        ErrorFormat::Annotated => Box::new(AnnotatedEmitter),
+    }
+}
+
+/// Shared configuration threaded through the build pipeline. Cloned once
+/// per call to `process_dir`/`process_file` so that defaults picked up from
+/// the environment (e.g. `OUT_DIR`, `CARGO_FEATURE_*`) don't leak back into
+/// the `Configuration` the caller holds.
+#[derive(Clone)]
+pub struct Session {
+    pub color_config: ColorConfig,
+    pub in_dir: Option<PathBuf>,
+    pub out_dir: Option<PathBuf>,
+    pub force_build: bool,
+    pub use_fingerprint_cache: bool,
+    pub emit_rerun_directives: bool,
+    pub emit_comments: bool,
+    pub emit_whitespace: bool,
+    pub emit_report: bool,
+    pub emit_json_diagnostics: bool,
+    pub emit_self_profile: bool,
+    pub log: Log,
+    pub macro_recursion_limit: u16,
+    pub use_jobserver: bool,
+    pub parallelism: usize,
+    pub features: Option<HashSet<String>>,
+    pub unit_test: bool,
+    pub error_format: ErrorFormat,
+    pub(crate) diagnostics_writer: DiagnosticsWriter,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            color_config: ColorConfig::default(),
+            in_dir: None,
+            out_dir: None,
+            force_build: false,
+            use_fingerprint_cache: false,
+            emit_rerun_directives: false,
+            emit_comments: false,
+            emit_whitespace: true,
+            emit_report: false,
+            emit_json_diagnostics: false,
+            emit_self_profile: false,
+            log: Log::default(),
+            macro_recursion_limit: 200,
+            use_jobserver: false,
+            parallelism: 1,
+            features: None,
+            unit_test: false,
+            error_format: ErrorFormat::default(),
+            diagnostics_writer: DiagnosticsWriter::default(),
+        }
+    }
+}
+
+impl Session {
+    /// Reports a single diagnostic: a parse error, a duplicate-rule
+    /// conflict, an unresolved macro use, or an undefined nonterminal.
+    ///
+    /// When [`Session::emit_json_diagnostics`] is set, the diagnostic is
+    /// additionally written as a line of JSON, mirroring rustc's
+    /// `--error-format=json`, so that editors and language servers can
+    /// surface it inline instead of scraping the human-readable text. Both
+    /// the JSON record and the rendered text go to
+    /// [`Session::diagnostics_writer`] (stderr by default).
+    pub(crate) fn report(&self, diag: &Diagnostic) {
+        if self.emit_json_diagnostics {
+            self.diagnostics_writer.with_lock(|w| {
+                let _ = diag.write_json(w);
+            });
+        }
+
+        self.diagnostics_writer.with_lock(|w| {
+            let _ = emitter_for(self.error_format).emit(diag, w);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{DiagnosticSpan, Severity};
+
+    fn sample_diagnostic() -> Diagnostic {
+        Diagnostic::new("rule `Num` is defined more than once", Severity::Error)
+            .with_span(DiagnosticSpan {
+                file_name: "g.lalrpop".to_string(),
+                byte_start: 0,
+                byte_end: 3,
+                line_start: 1,
+                column_start: 1,
+                line_end: 1,
+                column_end: 4,
+                label: Some("this rule".to_string()),
+                source_line: Some("Num: () = ();".to_string()),
+            })
+    }
+
+    fn render(format: ErrorFormat) -> String {
+        let mut out = Vec::new();
+        emitter_for(format).emit(&sample_diagnostic(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn short_emitter_is_one_line_with_file_line_col() {
+        let rendered = render(ErrorFormat::Short);
+        assert_eq!(
+            rendered,
+            "g.lalrpop:1:1: rule `Num` is defined more than once\n"
+        );
+    }
+
+    #[test]
+    fn full_emitter_includes_severity_location_and_label() {
+        let rendered = render(ErrorFormat::Full);
+        assert!(rendered.starts_with("error: rule `Num` is defined more than once\n"));
+        assert!(rendered.contains("--> g.lalrpop:1:1"));
+        assert!(rendered.contains("this rule"));
+    }
+
+    #[test]
+    fn annotated_emitter_draws_a_caret_underline() {
+        let rendered = render(ErrorFormat::Annotated);
+        assert!(rendered.contains("Num: () = ();"));
+        // The span covers columns 1..4 ("Num"), so the underline should be
+        // three carets starting at the first column.
+        assert!(rendered.contains("^^^"));
+        assert!(rendered.contains("note: this rule"));
+    }
+
+    #[test]
+    fn diagnostics_writer_can_be_redirected_to_an_in_memory_buffer() {
+        let buffer: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let session = Session {
+            diagnostics_writer: DiagnosticsWriter::new(SharedBuffer(buffer.clone())),
+            ..Session::default()
+        };
+
+        session.report(&sample_diagnostic());
+
+        let written = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        assert!(written.contains("rule `Num` is defined more than once"));
+    }
+
+    /// A `Write` adapter over a shared `Vec<u8>`, so a test can inspect what
+    /// [`Session::report`] wrote after redirecting [`DiagnosticsWriter`].
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}